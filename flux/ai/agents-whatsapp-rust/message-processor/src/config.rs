@@ -0,0 +1,77 @@
+use shared::routing::KeywordRule;
+use std::env;
+
+#[derive(Clone)]
+pub struct Config {
+    pub mongodb_uri: String,
+    pub mongodb_database: String,
+    /// Optional read-only replica connection string for DLQ reads/statistics, so
+    /// dashboard/monitoring traffic doesn't compete with the write path.
+    pub mongodb_replica_uri: Option<String>,
+    pub redis_uri: String,
+    pub broker_url: String,
+    pub dlq_retry_interval_secs: u64,
+    pub idempotency_cache_capacity: usize,
+    pub idempotency_ttl_secs: u64,
+    /// FCM server key used to push offline receivers a wake-up notification. Push
+    /// dispatch is skipped entirely (logged once at startup) when unset.
+    pub fcm_server_key: Option<String>,
+    /// OTLP collector endpoint traces/metrics are exported to.
+    pub otlp_endpoint: String,
+    /// Fraction of traces sampled, in `[0.0, 1.0]`.
+    pub otlp_sampling_ratio: f64,
+    /// Minimum confidence an `AgentRouter` strategy must return before its route is
+    /// accepted; below this, the chain falls through to the next strategy.
+    pub agent_routing_confidence_threshold: f64,
+    /// Keyword -> agent_id rules for `KeywordIntentClassifier`, so new agents can be
+    /// onboarded by editing config rather than code.
+    pub agent_keyword_rules: Vec<KeywordRule>,
+    /// Agent assigned when no routing strategy produces a confident match.
+    pub default_agent_id: String,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            mongodb_uri: env::var("MONGODB_URI")
+                .unwrap_or_else(|_| "mongodb://localhost:27017".to_string()),
+            mongodb_database: env::var("MONGODB_DATABASE")
+                .unwrap_or_else(|_| "messaging_app".to_string()),
+            mongodb_replica_uri: env::var("MONGODB_REPLICA_URI").ok(),
+            redis_uri: env::var("REDIS_URI")
+                .unwrap_or_else(|_| "redis://localhost:6379".to_string()),
+            broker_url: env::var("BROKER_URL").unwrap_or_else(|_| {
+                "http://default-broker.homelab-services.svc.cluster.local".to_string()
+            }),
+            dlq_retry_interval_secs: env::var("DLQ_RETRY_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            idempotency_cache_capacity: env::var("IDEMPOTENCY_CACHE_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100_000),
+            idempotency_ttl_secs: env::var("IDEMPOTENCY_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(86_400),
+            fcm_server_key: env::var("FCM_SERVER_KEY").ok(),
+            otlp_endpoint: env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .unwrap_or_else(|_| "http://localhost:4317".to_string()),
+            otlp_sampling_ratio: env::var("OTEL_TRACES_SAMPLER_ARG")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1.0),
+            agent_routing_confidence_threshold: env::var("AGENT_ROUTING_CONFIDENCE_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.5),
+            agent_keyword_rules: env::var("AGENT_KEYWORD_RULES")
+                .ok()
+                .and_then(|v| serde_json::from_str(&v).ok())
+                .unwrap_or_default(),
+            default_agent_id: env::var("DEFAULT_AGENT_ID")
+                .unwrap_or_else(|_| "agent-bruno".to_string()),
+        })
+    }
+}
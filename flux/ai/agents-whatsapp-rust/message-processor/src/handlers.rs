@@ -0,0 +1,514 @@
+use crate::AppState;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use cloudevents::{AttributesReader, Event, EventBuilder};
+use mongodb::bson::doc;
+use serde::Deserialize;
+use shared::delivery::DeliveryOutcome;
+use shared::errors::AppError;
+use shared::models::{
+    DLQErrorType, MessagePayload, MessageStatus, MessageType, Presence, StoredMessage, UserStatus,
+    WebSocketMessage,
+};
+use shared::prekeys::FetchedPrekeyBundle;
+use shared::push::{PushJob, PushOutcome};
+use shared::routing::RoutingContext;
+use tracing::{error, info, warn, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const PRESENCE_KEY_PREFIX: &str = "presence:";
+
+/// Once a device's one-time prekey pool drops to this many remaining, it's nudged with
+/// a `WebSocketMessage::PrekeysLow` so it replenishes before the pool runs dry.
+const PREKEY_LOW_WATERMARK: usize = 5;
+
+pub async fn handle_event(
+    State(state): State<std::sync::Arc<AppState>>,
+    Json(event): Json<Event>,
+) -> std::result::Result<&'static str, shared::errors::AppError> {
+    // Only process messaging.message.received events
+    if event.ty() != "messaging.message.received" {
+        return Ok("OK");
+    }
+
+    // One root span per inbound CloudEvent, parented to whatever produced it (if it
+    // carried W3C traceparent/tracestate extension attributes) so a message can be
+    // traced from WebSocket ingress through agent dispatch.
+    let root_span = tracing::info_span!(
+        "handle_event",
+        conversation_id = tracing::field::Empty,
+        idempotency_key = tracing::field::Empty,
+        sequence_number = tracing::field::Empty,
+    );
+    let traceparent = event.extension("traceparent").map(|v| v.to_string());
+    let tracestate = event.extension("tracestate").map(|v| v.to_string());
+    root_span.set_parent(shared::telemetry::context_from_cloudevent_headers(
+        traceparent,
+        tracestate,
+    ));
+
+    handle_event_inner(state, event)
+        .instrument(root_span)
+        .await
+}
+
+async fn handle_event_inner(
+    state: std::sync::Arc<AppState>,
+    event: Event,
+) -> std::result::Result<&'static str, shared::errors::AppError> {
+    let routing_started_at = std::time::Instant::now();
+
+    // Extract message data
+    let data = event
+        .data()
+        .and_then(|d| match d {
+            cloudevents::Data::Json(v) => Some(v),
+            _ => None,
+        })
+        .ok_or_else(|| shared::errors::AppError::Validation("Missing event data".to_string()))?;
+
+    let idempotency_key = data
+        .get("idempotency_key")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| {
+            shared::errors::AppError::Validation("Missing idempotency_key".to_string())
+        })?;
+
+    tracing::Span::current().record("idempotency_key", idempotency_key);
+
+    // Check idempotency against the tiered local/Redis cache (defense in depth)
+    let is_duplicate = async { state.idempotency_cache.check_and_reserve(idempotency_key).await }
+        .instrument(tracing::info_span!("idempotency_lookup"))
+        .await?;
+
+    if is_duplicate {
+        shared::telemetry::metrics().duplicate_messages.add(1, &[]);
+        info!(
+            "Duplicate message detected (idempotency key: {})",
+            idempotency_key
+        );
+        return Ok("OK");
+    }
+
+    let conversation_id = data
+        .get("conversation_id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| {
+            shared::errors::AppError::Validation("Missing conversation_id".to_string())
+        })?;
+
+    tracing::Span::current().record("conversation_id", conversation_id);
+    if let Some(sequence_number) = data.get("sequence_number").and_then(|v| v.as_u64()) {
+        tracing::Span::current().record("sequence_number", sequence_number);
+    }
+
+    // Determine agent via the configured routing strategy chain, then persist the
+    // choice onto the conversation so subsequent messages in the thread stay sticky.
+    let agent_id = async {
+        let routing_ctx = RoutingContext {
+            conversation_id: conversation_id.to_string(),
+            user_id: data
+                .get("sender_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            message_type: MessageType::Text,
+            content: data
+                .get("content")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+        };
+
+        let agent_id = state.agent_router.resolve(&routing_ctx).await?;
+
+        let conv_collection = state
+            .db
+            .collection::<mongodb::bson::Document>("conversations");
+        conv_collection
+            .update_one(
+                doc! { "_id": conversation_id },
+                doc! {
+                    "$set": { "agent_id": &agent_id },
+                    "$setOnInsert": { "created_at": Utc::now() },
+                },
+                mongodb::options::UpdateOptions::builder()
+                    .upsert(true)
+                    .build(),
+            )
+            .await?;
+
+        Ok::<_, shared::errors::AppError>(agent_id)
+    }
+    .instrument(tracing::info_span!("resolve_agent"))
+    .await?;
+
+    // Create agent.message CloudEvent
+    let agent_event = cloudevents::EventBuilderV10::new()
+        .id(uuid::Uuid::new_v4().to_string())
+        .source(state.config.broker_url.clone())
+        .ty("agent.message")
+        .data("application/json", data.clone())
+        .build()
+        .map_err(|e| {
+            shared::errors::AppError::Internal(format!("Failed to build CloudEvent: {}", e))
+        })?;
+
+    // Publish to Knative Broker with retry and DLQ
+    let broker_url = state.config.broker_url.clone();
+    let event_data_clone = data.clone();
+    let conversation_id_clone = conversation_id.to_string();
+    let publish_span = tracing::info_span!("publish_to_broker");
+
+    tokio::spawn(
+        async move {
+            if let Err(e) = publish_agent_event_with_retry(
+                &broker_url,
+                &agent_event,
+                &event_data_clone,
+                &conversation_id_clone,
+                &state,
+            )
+            .await
+            {
+                error!("Failed to publish agent message after retries: {}", e);
+            } else {
+                shared::telemetry::metrics()
+                    .routing_latency_ms
+                    .record(routing_started_at.elapsed().as_secs_f64() * 1000.0, &[]);
+                info!("Routed message to agent: {}", agent_id);
+            }
+        }
+        .instrument(publish_span),
+    );
+
+    // Deliver to the receiver's live connection if the fleet has one, wherever it's
+    // pinned; otherwise push them, since they're offline. Best-effort: push failures land
+    // in the DLQ, other failures are only logged, and none reach the caller.
+    if let Some(receiver_id) = data.get("receiver_id").and_then(|v| v.as_str()) {
+        let state = state.clone();
+        let receiver_id = receiver_id.to_string();
+        let conversation_id = conversation_id.to_string();
+        let message_id = data
+            .get("message_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let content = data
+            .get("content")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        tokio::spawn(
+            async move {
+                deliver_or_push(
+                    &state,
+                    receiver_id,
+                    conversation_id,
+                    message_id,
+                    content,
+                )
+                .await;
+            }
+            .in_current_span(),
+        );
+    }
+
+    Ok("OK")
+}
+
+/// Tries to deliver straight to the receiver's live connection wherever it's pinned in
+/// the fleet; if they have none, falls back to a push notification. Push failures are
+/// recorded in the DLQ under `DLQErrorType::PushFailed`; delivery-lookup and presence
+/// errors are only logged. Nothing is propagated, since a missed notification shouldn't
+/// block message routing.
+async fn deliver_or_push(
+    state: &std::sync::Arc<AppState>,
+    receiver_id: String,
+    conversation_id: String,
+    message_id: String,
+    content: String,
+) {
+    let ws_message = WebSocketMessage::Message {
+        client_message_id: None,
+        idempotency_key: message_id.clone(),
+        payload: MessagePayload {
+            conversation_id: conversation_id.clone(),
+            receiver_id: receiver_id.clone(),
+            content,
+            message_type: MessageType::Text,
+            media_url: None,
+            reply_to_message_id: None,
+            timestamp: Utc::now().timestamp(),
+        },
+    };
+
+    match state.delivery.send_to_user(&receiver_id, ws_message).await {
+        Ok(DeliveryOutcome::Delivered) => return,
+        Ok(DeliveryOutcome::NoConnection) => {}
+        Err(e) => {
+            warn!("Cross-instance delivery lookup failed for {}: {}", receiver_id, e);
+        }
+    }
+
+    let Some(dispatcher) = &state.push_dispatcher else {
+        return;
+    };
+
+    match is_receiver_online(state, &receiver_id).await {
+        Ok(true) => return,
+        Ok(false) => {}
+        Err(e) => {
+            warn!("Failed to check presence for {}: {}", receiver_id, e);
+            return;
+        }
+    }
+
+    let job = PushJob {
+        user_id: receiver_id.clone(),
+        conversation_id: conversation_id.clone(),
+        message_id: message_id.clone(),
+        message_type: MessageType::Text,
+        always_encrypted: true,
+    };
+
+    let outcome = dispatcher.dispatch(job).await;
+    let failure = match outcome {
+        Ok(PushOutcome::Delivered) | Ok(PushOutcome::NoRegisteredDevices) => return,
+        Ok(PushOutcome::Failed(reason)) => reason,
+        Err(e) => e.to_string(),
+    };
+
+    warn!("Push delivery failed for {}: {}", receiver_id, failure);
+
+    let message_for_dlq = StoredMessage {
+        message_id: Some(message_id.clone()),
+        idempotency_key: String::new(),
+        conversation_id,
+        sequence_number: 0,
+        sender_id: String::new(),
+        receiver_id,
+        message_type: MessageType::Text,
+        content: String::new(),
+        media_url: None,
+        reply_to_message_id: None,
+        timestamp: Utc::now(),
+        status: MessageStatus::Sent,
+        created_at: Utc::now(),
+    };
+
+    let dlq = state.dlq();
+    shared::telemetry::metrics().dlq_insertions.add(1, &[]);
+    let _ = dlq
+        .add(message_for_dlq, failure, DLQErrorType::PushFailed, 0)
+        .await;
+}
+
+async fn is_receiver_online(
+    state: &std::sync::Arc<AppState>,
+    user_id: &str,
+) -> Result<bool, shared::errors::AppError> {
+    let mut conn = state.redis.get_multiplexed_async_connection().await?;
+    let raw: Option<String> = redis::cmd("GET")
+        .arg(format!("{}{}", PRESENCE_KEY_PREFIX, user_id))
+        .query_async(&mut conn)
+        .await?;
+
+    let Some(raw) = raw else {
+        return Ok(false);
+    };
+
+    let presence: Presence = serde_json::from_str(&raw)?;
+    Ok(presence.status == UserStatus::Online)
+}
+
+async fn publish_agent_event_with_retry(
+    broker_url: &str,
+    event: &cloudevents::Event,
+    event_data: &serde_json::Value,
+    conversation_id: &str,
+    state: &std::sync::Arc<AppState>,
+) -> Result<(), shared::errors::AppError> {
+    let max_retries = 5;
+    let mut retry_count = 0;
+    let mut backoff_ms = 100;
+
+    // Create a StoredMessage-like structure for DLQ (if needed)
+    let message_for_dlq = StoredMessage {
+        message_id: event_data
+            .get("message_id")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        idempotency_key: event_data
+            .get("idempotency_key")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        conversation_id: conversation_id.to_string(),
+        sequence_number: event_data
+            .get("sequence_number")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+        sender_id: event_data
+            .get("sender_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        receiver_id: event_data
+            .get("receiver_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        message_type: MessageType::Text,
+        content: event_data
+            .get("content")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        media_url: None,
+        reply_to_message_id: None,
+        timestamp: Utc::now(),
+        status: MessageStatus::Sent,
+        created_at: Utc::now(),
+    };
+
+    loop {
+        let attempt_span = tracing::info_span!("publish_attempt", attempt = retry_count + 1);
+        let attempt_started_at = std::time::Instant::now();
+
+        let outcome = async {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .map_err(|e| {
+                    shared::errors::AppError::Internal(format!(
+                        "Failed to create HTTP client: {}",
+                        e
+                    ))
+                })?;
+
+            Ok::<_, shared::errors::AppError>(client.post(broker_url).json(event).send().await)
+        }
+        .instrument(attempt_span)
+        .await?;
+
+        shared::telemetry::metrics()
+            .publish_latency_ms
+            .record(attempt_started_at.elapsed().as_secs_f64() * 1000.0, &[]);
+
+        match outcome {
+            Ok(response) if response.status().is_success() => {
+                return Ok(());
+            }
+            Ok(response) => {
+                let error_msg = format!("Broker returned error status: {}", response.status());
+                if retry_count >= max_retries {
+                    // Hand off to the DLQ at retry_count 0, not the exhausted inline
+                    // count: the reaper owns the backoff schedule from here, and an
+                    // entry inserted at `max_retries` would land `Failed` with no
+                    // `next_retry_at`, so `claim_next_due_entry` could never pick it up.
+                    let dlq = state.dlq();
+                    shared::telemetry::metrics().dlq_insertions.add(1, &[]);
+                    let _ = dlq
+                        .add(
+                            message_for_dlq.clone(),
+                            error_msg.clone(),
+                            DLQErrorType::BrokerPublishFailed,
+                            0,
+                        )
+                        .await;
+                    return Err(shared::errors::AppError::Internal(error_msg));
+                }
+                retry_count += 1;
+                shared::telemetry::metrics().publish_retries.add(1, &[]);
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to publish to broker: {}", e);
+                if retry_count >= max_retries {
+                    // Hand off to the DLQ at retry_count 0, not the exhausted inline
+                    // count: the reaper owns the backoff schedule from here, and an
+                    // entry inserted at `max_retries` would land `Failed` with no
+                    // `next_retry_at`, so `claim_next_due_entry` could never pick it up.
+                    let dlq = state.dlq();
+                    shared::telemetry::metrics().dlq_insertions.add(1, &[]);
+                    let _ = dlq
+                        .add(
+                            message_for_dlq.clone(),
+                            error_msg.clone(),
+                            DLQErrorType::BrokerPublishFailed,
+                            0,
+                        )
+                        .await;
+                    return Err(shared::errors::AppError::Internal(error_msg));
+                }
+                retry_count += 1;
+                shared::telemetry::metrics().publish_retries.add(1, &[]);
+            }
+        }
+
+        // Exponential backoff with jitter
+        let jitter = rand::random::<u64>() % 50;
+        let delay_ms = backoff_ms + jitter;
+        tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+
+        backoff_ms = (backoff_ms as f64 * 2.0).min(30000.0) as u64;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishPrekeyBundleRequest {
+    pub identity_key: String,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String,
+    pub one_time_prekeys: Vec<String>,
+}
+
+/// Uploads or rotates `device_id`'s X3DH key bundle.
+pub async fn publish_prekey_bundle(
+    State(state): State<std::sync::Arc<AppState>>,
+    Path((user_id, device_id)): Path<(String, String)>,
+    Json(req): Json<PublishPrekeyBundleRequest>,
+) -> Result<&'static str, AppError> {
+    state
+        .prekeys
+        .publish(
+            &user_id,
+            &device_id,
+            req.identity_key,
+            req.signed_prekey,
+            req.signed_prekey_signature,
+            req.one_time_prekeys,
+        )
+        .await?;
+    Ok("OK")
+}
+
+/// Returns `device_id`'s bundle for a requester to start an E2EE session with it,
+/// consuming one one-time prekey in the process. Nudges the device to replenish once
+/// its pool runs low.
+pub async fn fetch_prekey_bundle(
+    State(state): State<std::sync::Arc<AppState>>,
+    Path((user_id, device_id)): Path<(String, String)>,
+) -> Result<Json<FetchedPrekeyBundle>, AppError> {
+    let result = state
+        .prekeys
+        .fetch(&user_id, &device_id)
+        .await?
+        .ok_or(AppError::DeviceNotFound)?;
+
+    if result.remaining_one_time_prekeys <= PREKEY_LOW_WATERMARK {
+        let notice = WebSocketMessage::PrekeysLow {
+            device_id: device_id.clone(),
+            remaining: result.remaining_one_time_prekeys as u64,
+        };
+        if let Err(e) = state.delivery.send_to_user(&user_id, notice).await {
+            warn!("Failed to notify {} of low prekey pool: {}", user_id, e);
+        }
+    }
+
+    Ok(Json(result.bundle))
+}
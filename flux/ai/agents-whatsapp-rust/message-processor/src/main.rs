@@ -1,39 +1,88 @@
 use axum::{routing::post, Router};
 use mongodb::{Client as MongoClient, Database};
 use redis::Client as RedisClient;
+use shared::delivery::DeliverySender;
+use shared::dlq::DeadLetterQueue;
+use shared::idempotency::IdempotencyCache;
+use shared::prekeys::PrekeyRegistry;
+use shared::push::{DeviceTokenRegistry, FcmPushProvider, PushDispatcher};
+use shared::routing::{
+    AgentRouterChain, DefaultAgentRouter, ExplicitPreferenceRouter, KeywordIntentClassifier,
+    StickyConversationRouter,
+};
 use std::sync::Arc;
-use tracing::info;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
 mod config;
 mod handlers;
 
 use config::Config;
 
-#[derive(Clone)]
 pub struct AppState {
     pub mongo: MongoClient,
     pub db: Database,
+    /// Read-only replica database for DLQ reads/statistics, when `MONGODB_REPLICA_URI`
+    /// is configured.
+    pub replica_db: Option<Database>,
     pub redis: RedisClient,
     pub config: Config,
+    pub idempotency_cache: IdempotencyCache,
+    /// Dispatches push notifications to offline receivers. `None` when `FCM_SERVER_KEY`
+    /// is unset, in which case offline receivers simply don't get a push.
+    pub push_dispatcher: Option<PushDispatcher>,
+    /// Chain of strategies used to assign a new or unrouted conversation to an agent.
+    pub agent_router: AgentRouterChain,
+    /// Publishes to a receiver's owning WebSocket-gateway instance when they have a
+    /// live connection pinned elsewhere in the fleet.
+    pub delivery: DeliverySender,
+    /// Registry of per-device X3DH prekey bundles backing E2EE session establishment.
+    pub prekeys: PrekeyRegistry,
+}
+
+impl AppState {
+    /// Build a `DeadLetterQueue` wired to this state's primary database and, if
+    /// configured, its read replica.
+    pub fn dlq(&self) -> DeadLetterQueue {
+        let dlq = DeadLetterQueue::new(self.db.clone(), self.redis.clone(), self.config.broker_url.clone());
+        match &self.replica_db {
+            Some(replica_db) => dlq.with_read_replica(replica_db.clone()),
+            None => dlq,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter("message_processor=info")
-        .init();
-
-    info!("Starting message-processor...");
-
     // Load configuration
     let config = Config::from_env()?;
 
+    // Initialize tracing, exporting spans/metrics over OTLP (logs go to stdout)
+    shared::telemetry::init_telemetry(
+        "message-processor",
+        &shared::telemetry::TelemetryConfig {
+            otlp_endpoint: config.otlp_endpoint.clone(),
+            sampling_ratio: config.otlp_sampling_ratio,
+        },
+    )?;
+
+    info!("Starting message-processor...");
+
     // Connect to MongoDB
     let mongo = MongoClient::with_uri_str(&config.mongodb_uri).await?;
     let db = mongo.database(&config.mongodb_database);
     info!("Connected to MongoDB");
 
+    // Connect to an optional read replica for DLQ reads/statistics
+    let replica_db = match &config.mongodb_replica_uri {
+        Some(uri) => {
+            let replica_mongo = MongoClient::with_uri_str(uri).await?;
+            info!("Connected to MongoDB read replica");
+            Some(replica_mongo.database(&config.mongodb_database))
+        }
+        None => None,
+    };
+
     // Connect to Redis
     let redis = RedisClient::open(config.redis_uri.as_str())?;
     info!("Connected to Redis");
@@ -43,16 +92,72 @@ async fn main() -> anyhow::Result<()> {
     info!("Initialized DLQ collection");
 
     // Create app state
+    let idempotency_cache = IdempotencyCache::new(
+        redis.clone(),
+        config.idempotency_cache_capacity,
+        config.idempotency_ttl_secs,
+    );
+
+    let push_dispatcher = match &config.fcm_server_key {
+        Some(server_key) => {
+            info!("Push notifications enabled (FCM)");
+            Some(PushDispatcher::new(
+                Arc::new(FcmPushProvider::new(server_key.clone())),
+                DeviceTokenRegistry::new(db.clone()),
+            ))
+        }
+        None => {
+            warn!("FCM_SERVER_KEY not set; offline receivers will not get a push notification");
+            None
+        }
+    };
+
+    // Strategies are tried in priority order: explicit user preference, then
+    // conversation stickiness, then keyword intent classification, then a hard
+    // default. Stickiness must outrank the keyword classifier, not just the default:
+    // it always returns confidence 1.0 once a conversation has an agent_id, so placing
+    // it ahead of the classifier is what actually keeps a thread pinned instead of
+    // letting a later message that happens to contain a configured keyword bounce it.
+    let agent_router = AgentRouterChain::new(
+        vec![
+            Box::new(ExplicitPreferenceRouter::new(db.clone())),
+            Box::new(StickyConversationRouter::new(db.clone())),
+            Box::new(KeywordIntentClassifier::new(config.agent_keyword_rules.clone())),
+            Box::new(DefaultAgentRouter::new(config.default_agent_id.clone())),
+        ],
+        config.agent_routing_confidence_threshold,
+    );
+
+    let delivery = DeliverySender::new(redis.clone());
+    let prekeys = PrekeyRegistry::new(db.clone());
+
     let state = Arc::new(AppState {
         mongo,
         db,
+        replica_db,
         redis,
         config,
+        idempotency_cache,
+        push_dispatcher,
+        agent_router,
+        delivery,
+        prekeys,
     });
 
+    // Cancellation token shared by the HTTP server and the DLQ retry scheduler so a
+    // Kubernetes pod termination lets both drain in-flight work instead of being abandoned.
+    let shutdown_token = CancellationToken::new();
+
+    let dlq_task = tokio::spawn(run_dlq_retry_scheduler(state.clone(), shutdown_token.clone()));
+
     // Build router
     let app = Router::new()
         .route("/", post(handlers::handle_event))
+        .route(
+            "/prekeys/:user_id/:device_id",
+            axum::routing::put(handlers::publish_prekey_bundle)
+                .get(handlers::fetch_prekey_bundle),
+        )
         .route("/health/live", axum::routing::get(|| async { "OK" }))
         .route("/health/ready", axum::routing::get(|| async { "OK" }))
         .with_state(state);
@@ -62,7 +167,79 @@ async fn main() -> anyhow::Result<()> {
     info!("Listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+
+    let serve_shutdown_token = shutdown_token.clone();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            info!("Shutdown signal received, cancelling background tasks");
+            serve_shutdown_token.cancel();
+        })
+        .await?;
+
+    // In case the server returned without its own shutdown future having cancelled the
+    // token, make sure the retry loop also winds down.
+    shutdown_token.cancel();
+
+    if let Err(e) = dlq_task.await {
+        error!("DLQ retry scheduler task panicked: {}", e);
+    }
+
+    opentelemetry::global::shutdown_tracer_provider();
 
     Ok(())
 }
+
+/// Periodically drives `DeadLetterQueue::retry_pending_messages` and `cleanup_expired`
+/// until `shutdown_token` is cancelled, letting an in-flight retry batch finish draining
+/// before the process exits.
+async fn run_dlq_retry_scheduler(state: Arc<AppState>, shutdown_token: CancellationToken) {
+    let dlq = state.dlq();
+    let interval = std::time::Duration::from_secs(state.config.dlq_retry_interval_secs);
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                match dlq.retry_pending_messages().await {
+                    Ok(count) if count > 0 => info!("DLQ retry pass requeued {} message(s)", count),
+                    Ok(_) => {}
+                    Err(e) => error!("DLQ retry pass failed: {}", e),
+                }
+
+                if let Err(e) = dlq.cleanup_expired().await {
+                    error!("DLQ cleanup pass failed: {}", e);
+                }
+            }
+            _ = shutdown_token.cancelled() => {
+                info!("DLQ retry scheduler draining and shutting down");
+                break;
+            }
+        }
+    }
+}
+
+/// Waits for either SIGTERM (Kubernetes pod termination) or ctrl-c.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install ctrl-c handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
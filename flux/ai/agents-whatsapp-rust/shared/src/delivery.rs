@@ -0,0 +1,192 @@
+use crate::errors::AppResult;
+use crate::models::{ConnectionRegistry, WebSocketMessage};
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use redis::Client as RedisClient;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+const CONNECTION_REGISTRY_KEY_PREFIX: &str = "conn:";
+const INSTANCE_CHANNEL_PREFIX: &str = "instance:";
+
+/// Tracks, per `receiver_id`, which WebSocket-gateway instance and connection currently
+/// hold their live socket. Backed by Redis so any instance that processes a CloudEvent
+/// can find a receiver pinned to a different replica.
+pub struct ConnectionDirectory {
+    redis: RedisClient,
+}
+
+impl ConnectionDirectory {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    pub async fn register(&self, user_id: &str, registry: &ConnectionRegistry) -> AppResult<()> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let payload = serde_json::to_string(registry)?;
+        conn.set::<_, _, ()>(
+            format!("{}{}", CONNECTION_REGISTRY_KEY_PREFIX, user_id),
+            payload,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn lookup(&self, user_id: &str) -> AppResult<Option<ConnectionRegistry>> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let raw: Option<String> = conn
+            .get(format!("{}{}", CONNECTION_REGISTRY_KEY_PREFIX, user_id))
+            .await?;
+
+        Ok(match raw {
+            Some(raw) => Some(serde_json::from_str(&raw)?),
+            None => None,
+        })
+    }
+
+    /// Removes the registry entry for `user_id`, called when their socket closes so a
+    /// later lookup doesn't route to a connection that no longer exists.
+    pub async fn deregister(&self, user_id: &str) -> AppResult<()> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        conn.del::<_, ()>(format!("{}{}", CONNECTION_REGISTRY_KEY_PREFIX, user_id))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Wire payload published to an instance's pub/sub channel: the message plus which
+/// local connection it's destined for, since a channel is shared by every connection
+/// pinned to that instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InstanceEnvelope {
+    connection_id: String,
+    message: WebSocketMessage,
+}
+
+/// Outcome of attempting to deliver a message to a user's live connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Delivered,
+    NoConnection,
+}
+
+/// Publish-side of cross-instance delivery: looks up which instance owns a receiver's
+/// connection and publishes the message to that instance's Redis pub/sub channel.
+pub struct DeliverySender {
+    redis: RedisClient,
+    directory: ConnectionDirectory,
+}
+
+impl DeliverySender {
+    pub fn new(redis: RedisClient) -> Self {
+        Self {
+            directory: ConnectionDirectory::new(redis.clone()),
+            redis,
+        }
+    }
+
+    pub async fn send_to_user(
+        &self,
+        receiver_id: &str,
+        message: WebSocketMessage,
+    ) -> AppResult<DeliveryOutcome> {
+        let Some(registry) = self.directory.lookup(receiver_id).await? else {
+            return Ok(DeliveryOutcome::NoConnection);
+        };
+
+        let envelope = InstanceEnvelope {
+            connection_id: registry.connection_id,
+            message,
+        };
+        let payload = serde_json::to_string(&envelope)?;
+
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        conn.publish::<_, _, ()>(
+            format!("{}{}", INSTANCE_CHANNEL_PREFIX, registry.instance_id),
+            payload,
+        )
+        .await?;
+
+        Ok(DeliveryOutcome::Delivered)
+    }
+}
+
+/// In-process map from `connection_id` to that connection's outbound sender, so the
+/// pub/sub listener can route a payload to exactly the right socket task. Held by a
+/// WebSocket-gateway instance; removing a connection (on socket close) drops its
+/// sender, which in turn ends that connection's write loop.
+#[derive(Default)]
+pub struct ConnectionHub {
+    connections: Mutex<HashMap<String, UnboundedSender<WebSocketMessage>>>,
+}
+
+impl ConnectionHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn insert(&self, connection_id: String, sender: UnboundedSender<WebSocketMessage>) {
+        self.connections.lock().await.insert(connection_id, sender);
+    }
+
+    /// Removes a connection's sender. Call this when a socket closes, alongside
+    /// `ConnectionDirectory::deregister`, so neither leaks past the connection's life.
+    pub async fn remove(&self, connection_id: &str) {
+        self.connections.lock().await.remove(connection_id);
+    }
+
+    async fn dispatch(&self, connection_id: &str, message: WebSocketMessage) -> bool {
+        self.connections
+            .lock()
+            .await
+            .get(connection_id)
+            .map(|sender| sender.send(message).is_ok())
+            .unwrap_or(false)
+    }
+}
+
+/// Subscribes to this instance's Redis pub/sub channel and forwards every received
+/// envelope to the matching connection in `hub`, until the connection drops or the
+/// subscription errors. Runs for the lifetime of the gateway process.
+pub async fn run_instance_subscriber(
+    redis: RedisClient,
+    instance_id: String,
+    hub: Arc<ConnectionHub>,
+) -> AppResult<()> {
+    let channel = format!("{}{}", INSTANCE_CHANNEL_PREFIX, instance_id);
+    let conn = redis.get_async_connection().await?;
+    let mut pubsub = conn.into_pubsub();
+    pubsub.subscribe(&channel).await?;
+
+    let mut stream = pubsub.on_message();
+    while let Some(msg) = stream.next().await {
+        let payload: String = match msg.get_payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to read instance channel payload: {}", e);
+                continue;
+            }
+        };
+
+        let envelope: InstanceEnvelope = match serde_json::from_str(&payload) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                warn!("Failed to decode instance channel envelope: {}", e);
+                continue;
+            }
+        };
+
+        if !hub.dispatch(&envelope.connection_id, envelope.message).await {
+            warn!(
+                "No local connection {} to deliver to on instance {}",
+                envelope.connection_id, instance_id
+            );
+        }
+    }
+
+    Ok(())
+}
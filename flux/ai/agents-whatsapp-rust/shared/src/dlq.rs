@@ -1,32 +1,76 @@
 use crate::errors::{AppError, AppResult};
-use crate::models::{DLQErrorType, DLQStatus, DeadLetterQueueEntry, StoredMessage};
+use crate::models::{DLQErrorType, DLQStatus, DeadLetterQueueEntry, MessageReceivedEvent, StoredMessage};
+use crate::utils::retry_with_backoff;
 use chrono::{Duration, Utc};
 use mongodb::bson::{doc, Document};
 use mongodb::{Database, IndexModel};
+use redis::Client as RedisClient;
 use std::time::Duration as StdDuration;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
+
+/// Max attempts and base backoff for `retry_with_backoff`-wrapped DLQ database
+/// operations, so a brief MongoDB leader change doesn't bubble a 500 to the caller.
+const DB_RETRY_MAX_ATTEMPTS: u32 = 3;
+const DB_RETRY_BASE_MS: u64 = 50;
+
+/// Redis key used to coordinate the single-instance DLQ retry lock across replicas.
+const RETRY_LOCK_KEY: &str = "dlq:retry_pending:lock";
+
+/// Lua script that only deletes the lock if it still holds our token, so a replica
+/// never releases a lock that already expired and was re-acquired by another instance.
+const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("del", KEYS[1])
+else
+    return 0
+end
+"#;
 
 /// Dead Letter Queue manager
 pub struct DeadLetterQueue {
     db: Database,
+    /// Database used for read-only queries. Defaults to `db`, but is routed to an
+    /// optional read replica when one is configured so dashboard/monitoring traffic
+    /// doesn't compete with the write path.
+    read_db: Database,
+    redis: RedisClient,
+    broker_url: String,
     max_retries: u32,
     retry_backoff_base_ms: u64,
     retry_backoff_multiplier: f64,
     dlq_ttl_days: i64,
+    retry_lock_ttl_ms: u64,
 }
 
 impl DeadLetterQueue {
-    pub fn new(db: Database) -> Self {
+    pub fn new(db: Database, redis: RedisClient, broker_url: String) -> Self {
         Self {
+            read_db: db.clone(),
             db,
+            redis,
+            broker_url,
             max_retries: 5,
             retry_backoff_base_ms: 100,
             retry_backoff_multiplier: 2.0,
             dlq_ttl_days: 7,
+            // Must comfortably exceed a single retry batch's expected duration so a
+            // crashed holder's lock auto-expires instead of deadlocking the cluster.
+            retry_lock_ttl_ms: 60_000,
         }
     }
 
-    /// Add a failed message to DLQ
+    /// Route read-only DLQ queries (`get_entries_by_status`, `get_statistics`) to a
+    /// secondary database, leaving `add`/`mark_resolved`/`cleanup_expired` on the primary.
+    pub fn with_read_replica(mut self, read_db: Database) -> Self {
+        self.read_db = read_db;
+        self
+    }
+
+    /// Add a failed message to DLQ.
+    ///
+    /// Always a single `insert_one`: message-processor handles exactly one inbound
+    /// message per HTTP request, so there's no batch boundary (e.g. a bulk webhook
+    /// delivery) anywhere upstream to amortize a bulk insert against.
     pub async fn add(
         &self,
         message: StoredMessage,
@@ -34,6 +78,30 @@ impl DeadLetterQueue {
         error_type: DLQErrorType,
         retry_count: u32,
     ) -> AppResult<String> {
+        let (dlq_id, doc) = self.build_entry_document(message, error.clone(), error_type, retry_count)?;
+
+        let collection = self.db.collection::<Document>("dead_letter_queue");
+        retry_with_backoff("dlq.insert_one", DB_RETRY_MAX_ATTEMPTS, DB_RETRY_BASE_MS, || async {
+            collection.insert_one(doc.clone(), None).await.map_err(AppError::Database)
+        })
+        .await?;
+
+        info!(
+            "Added message to DLQ: id={}, retry_count={}, error={}",
+            dlq_id, retry_count, error
+        );
+
+        Ok(dlq_id)
+    }
+
+    /// Build the BSON document (with `expires_at` TTL field) for a single DLQ entry.
+    fn build_entry_document(
+        &self,
+        message: StoredMessage,
+        error: String,
+        error_type: DLQErrorType,
+        retry_count: u32,
+    ) -> AppResult<(String, Document)> {
         let dlq_id = uuid::Uuid::new_v4().to_string();
 
         let next_retry_at = if retry_count < self.max_retries {
@@ -46,7 +114,7 @@ impl DeadLetterQueue {
         let entry = DeadLetterQueueEntry {
             id: Some(dlq_id.clone()),
             message,
-            error: error.clone(),
+            error,
             error_type,
             retry_count,
             max_retries: self.max_retries,
@@ -62,7 +130,6 @@ impl DeadLetterQueue {
             resolved_reason: None,
         };
 
-        let collection = self.db.collection::<Document>("dead_letter_queue");
         let mut doc = mongodb::bson::to_document(&entry)
             .map_err(|e| AppError::Internal(format!("Failed to serialize DLQ entry: {}", e)))?;
 
@@ -70,66 +137,274 @@ impl DeadLetterQueue {
         let expires_at = Utc::now() + Duration::days(self.dlq_ttl_days);
         doc.insert("expires_at", expires_at);
 
-        collection.insert_one(doc, None).await?;
+        Ok((dlq_id, doc))
+    }
 
-        info!(
-            "Added message to DLQ: id={}, retry_count={}, error={}",
-            dlq_id, retry_count, error
-        );
+    /// Retry failed messages that are due for retry. Acquires a single-instance
+    /// distributed lock first so that when the message-processor runs with multiple
+    /// replicas, only one of them drains the DLQ per pass.
+    pub async fn retry_pending_messages(&self) -> AppResult<usize> {
+        let lock_token = match self.acquire_retry_lock().await? {
+            Some(token) => token,
+            None => {
+                info!("Another instance holds the DLQ retry lock; skipping this pass");
+                return Ok(0);
+            }
+        };
 
-        Ok(dlq_id)
+        let result = self.retry_pending_messages_locked().await;
+
+        if let Err(e) = self.release_retry_lock(&lock_token).await {
+            warn!("Failed to release DLQ retry lock: {}", e);
+        }
+
+        result
     }
 
-    /// Retry failed messages that are due for retry
-    pub async fn retry_pending_messages(&self) -> AppResult<usize> {
+    /// Try to acquire the cluster-wide retry lock with `SET key token NX PX ttl`,
+    /// returning our randomly generated token on success.
+    async fn acquire_retry_lock(&self) -> AppResult<Option<String>> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(RETRY_LOCK_KEY)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.retry_lock_ttl_ms)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(acquired.map(|_| token))
+    }
+
+    /// Release the retry lock, but only if it still holds our token (it may have
+    /// already expired and been re-acquired by another instance).
+    async fn release_retry_lock(&self, token: &str) -> AppResult<()> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let _: i32 = redis::Script::new(RELEASE_LOCK_SCRIPT)
+            .key(RETRY_LOCK_KEY)
+            .arg(token)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Drains every due entry one at a time, atomically claiming each via
+    /// `find_one_and_update` so that even if the cluster-wide retry lock expired and a
+    /// second instance started a pass concurrently, the same entry can't be replayed
+    /// twice.
+    async fn retry_pending_messages_locked(&self) -> AppResult<usize> {
+        let mut count = 0;
+
+        while let Some(entry) = self.claim_next_due_entry().await? {
+            let id = match &entry.id {
+                Some(id) => id.clone(),
+                None => continue,
+            };
+
+            match self.republish(&entry.message).await {
+                Ok(()) => {
+                    info!(
+                        "Redelivered DLQ message: id={}, retry_count={}",
+                        id, entry.retry_count
+                    );
+                    crate::telemetry::metrics().dlq_replayed.add(1, &[]);
+                    self.mark_resolved(&id, Some("redelivered by reaper".to_string()))
+                        .await?;
+                    crate::telemetry::metrics().dlq_resolved.add(1, &[]);
+                }
+                Err(e) => {
+                    warn!(
+                        "Redelivery failed for DLQ message: id={}, retry_count={}, error={}",
+                        id, entry.retry_count, e
+                    );
+                    self.record_failed_retry(&id, &entry, e.to_string()).await?;
+                }
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Atomically claims the next due `Pending`/`Retrying` entry, transitioning it to
+    /// `Retrying` and returning the pre-update document so the caller still has the
+    /// original `retry_count`. Returns `None` once nothing is due.
+    ///
+    /// Only claims `BrokerPublishFailed` entries: `republish` redelivers by posting an
+    /// `agent.message` CloudEvent, which only makes sense for messages that actually
+    /// failed to reach the broker. Other error types (e.g. `PushFailed`, which is
+    /// recorded from a stub message with no real content) aren't broker-publishable and
+    /// must not be replayed as if they were.
+    async fn claim_next_due_entry(&self) -> AppResult<Option<DeadLetterQueueEntry>> {
         let collection = self.db.collection::<Document>("dead_letter_queue");
 
         let filter = doc! {
-            "status": "pending",
+            "status": { "$in": ["pending", "retrying"] },
             "next_retry_at": { "$lte": Utc::now() },
             "retry_count": { "$lt": self.max_retries },
+            "error_type": "broker_publish_failed",
         };
+        // Pushing `next_retry_at` out by the lock TTL leases the claim: a concurrent pass
+        // can't re-claim this `retrying` entry mid-replay, but if the claimer crashes the
+        // entry becomes due again once the lease runs out.
+        let update = doc! {
+            "$set": {
+                "status": "retrying",
+                "last_retry_at": Utc::now(),
+                "next_retry_at": Utc::now() + Duration::milliseconds(self.retry_lock_ttl_ms as i64),
+            }
+        };
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .sort(doc! { "next_retry_at": 1 })
+            .return_document(mongodb::options::ReturnDocument::Before)
+            .build();
+
+        let claimed = retry_with_backoff(
+            "dlq.find_one_and_update.claim",
+            DB_RETRY_MAX_ATTEMPTS,
+            DB_RETRY_BASE_MS,
+            || async {
+                collection
+                    .find_one_and_update(filter.clone(), update.clone(), options.clone())
+                    .await
+                    .map_err(AppError::Database)
+            },
+        )
+        .await?;
 
-        let mut count = 0;
-        let mut cursor = collection.find(filter, None).await?;
+        let Some(raw_doc) = claimed else {
+            return Ok(None);
+        };
 
-        while cursor.advance().await? {
-            let raw_doc = cursor.current();
-            let doc = mongodb::bson::from_slice(raw_doc.as_bytes())
-                .map_err(|e| AppError::Internal(format!("Failed to parse document: {}", e)))?;
+        let entry = mongodb::bson::from_document::<DeadLetterQueueEntry>(raw_doc)
+            .map_err(|e| AppError::Internal(format!("Failed to parse claimed DLQ entry: {}", e)))?;
 
-            if let Ok(entry) = mongodb::bson::from_document::<DeadLetterQueueEntry>(doc) {
-                // Update status to retrying
-                let id = entry
-                    .id
-                    .as_ref()
-                    .ok_or_else(|| AppError::Internal("DLQ entry missing id".to_string()))?;
-                collection
-                    .update_one(
-                        doc! { "_id": id },
-                        doc! {
-                            "$set": {
-                                "status": "retrying",
-                                "last_retry_at": Utc::now(),
-                            }
-                        },
-                        None,
-                    )
-                    .await?;
-
-                // Here you would trigger the retry logic
-                // For now, we'll just log it
-                info!(
-                    "Retrying DLQ message: id={}, retry_count={}",
-                    entry.id.as_ref().unwrap_or(&"unknown".to_string()),
-                    entry.retry_count
-                );
-
-                count += 1;
-            }
+        Ok(Some(entry))
+    }
+
+    /// Re-publish a DLQ'd message straight to the broker as the `agent.message`
+    /// CloudEvent that `publish_agent_event_with_retry` would have produced on a
+    /// successful publish. Deliberately does NOT re-post `messaging.message.received`:
+    /// that would re-enter `handle_event`, which reserves the same `idempotency_key`
+    /// before publishing and so would short-circuit this as a duplicate, mark the entry
+    /// resolved, and never actually re-route it to the agent.
+    async fn republish(&self, message: &StoredMessage) -> AppResult<()> {
+        let event_data = MessageReceivedEvent {
+            message_id: message.message_id.clone().unwrap_or_default(),
+            idempotency_key: message.idempotency_key.clone(),
+            conversation_id: message.conversation_id.clone(),
+            sender_id: message.sender_id.clone(),
+            receiver_id: message.receiver_id.clone(),
+            sequence_number: message.sequence_number,
+            message_type: message.message_type.clone(),
+            content: message.content.clone(),
+            timestamp: message.timestamp,
+        };
+
+        let event = cloudevents::EventBuilderV10::new()
+            .id(uuid::Uuid::new_v4().to_string())
+            .source(self.broker_url.clone())
+            .ty("agent.message")
+            .data(
+                "application/json",
+                serde_json::to_value(&event_data)
+                    .map_err(|e| AppError::Internal(format!("Failed to encode event: {}", e)))?,
+            )
+            .build()
+            .map_err(|e| AppError::Internal(format!("Failed to build CloudEvent: {}", e)))?;
+
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
+
+        let response = client
+            .post(&self.broker_url)
+            .json(&event)
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to publish to broker: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AppError::Internal(format!(
+                "Broker returned error status: {}",
+                response.status()
+            )));
         }
 
-        Ok(count)
+        Ok(())
+    }
+
+    /// Bump `retry_count` and reschedule `next_retry_at`, or give up once `max_retries`
+    /// is reached, leaving the entry `Failed`.
+    async fn record_failed_retry(
+        &self,
+        id: &str,
+        entry: &DeadLetterQueueEntry,
+        error: String,
+    ) -> AppResult<()> {
+        let collection = self.db.collection::<Document>("dead_letter_queue");
+        let retry_count = entry.retry_count + 1;
+
+        if retry_count >= self.max_retries {
+            retry_with_backoff(
+                "dlq.update_one.failed",
+                DB_RETRY_MAX_ATTEMPTS,
+                DB_RETRY_BASE_MS,
+                || async {
+                    collection
+                        .update_one(
+                            doc! { "_id": id },
+                            doc! {
+                                "$set": {
+                                    "status": "failed",
+                                    "retry_count": retry_count,
+                                    "error": &error,
+                                    "next_retry_at": mongodb::bson::Bson::Null,
+                                }
+                            },
+                            None,
+                        )
+                        .await
+                        .map_err(AppError::Database)
+                },
+            )
+            .await?;
+            crate::telemetry::metrics().dlq_exhausted.add(1, &[]);
+            error!("DLQ message exhausted retries: id={}", id);
+        } else {
+            let backoff_ms = self.calculate_backoff(retry_count);
+            let next_retry_at = Utc::now() + Duration::milliseconds(backoff_ms as i64);
+            retry_with_backoff(
+                "dlq.update_one.pending",
+                DB_RETRY_MAX_ATTEMPTS,
+                DB_RETRY_BASE_MS,
+                || async {
+                    collection
+                        .update_one(
+                            doc! { "_id": id },
+                            doc! {
+                                "$set": {
+                                    "status": "pending",
+                                    "retry_count": retry_count,
+                                    "error": &error,
+                                    "next_retry_at": next_retry_at,
+                                }
+                            },
+                            None,
+                        )
+                        .await
+                        .map_err(AppError::Database)
+                },
+            )
+            .await?;
+        }
+
+        Ok(())
     }
 
     /// Get DLQ entries by status
@@ -138,7 +413,7 @@ impl DeadLetterQueue {
         status: DLQStatus,
         limit: Option<i64>,
     ) -> AppResult<Vec<DeadLetterQueueEntry>> {
-        let collection = self.db.collection::<Document>("dead_letter_queue");
+        let collection = self.read_db.collection::<Document>("dead_letter_queue");
 
         let status_str = match status {
             DLQStatus::Pending => "pending",
@@ -211,32 +486,48 @@ impl DeadLetterQueue {
         Ok(count)
     }
 
-    /// Calculate exponential backoff delay in milliseconds
+    /// Calculate exponential backoff delay in milliseconds, with the same jitter and
+    /// 30s cap as `publish_agent_event_with_retry`'s broker-publish backoff.
     fn calculate_backoff(&self, retry_count: u32) -> u64 {
         let delay_ms = (self.retry_backoff_base_ms as f64)
             * self.retry_backoff_multiplier.powi(retry_count as i32);
+        let jitter_ms = rand::random::<u64>() % 50;
 
-        // Cap at 5 minutes
-        delay_ms.min(300_000.0) as u64
+        delay_ms.min(30_000.0) as u64 + jitter_ms
     }
 
     /// Get DLQ statistics
     pub async fn get_statistics(&self) -> AppResult<DLQStatistics> {
-        let collection = self.db.collection::<Document>("dead_letter_queue");
+        let collection = self.read_db.collection::<Document>("dead_letter_queue");
 
-        let total = collection.count_documents(doc! {}, None).await? as usize;
+        let total = retry_with_backoff("dlq.count.total", DB_RETRY_MAX_ATTEMPTS, DB_RETRY_BASE_MS, || async {
+            collection.count_documents(doc! {}, None).await.map_err(AppError::Database)
+        })
+        .await? as usize;
 
-        let pending = collection
-            .count_documents(doc! { "status": "pending" }, None)
-            .await? as usize;
+        let pending = retry_with_backoff("dlq.count.pending", DB_RETRY_MAX_ATTEMPTS, DB_RETRY_BASE_MS, || async {
+            collection
+                .count_documents(doc! { "status": "pending" }, None)
+                .await
+                .map_err(AppError::Database)
+        })
+        .await? as usize;
 
-        let failed = collection
-            .count_documents(doc! { "status": "failed" }, None)
-            .await? as usize;
+        let failed = retry_with_backoff("dlq.count.failed", DB_RETRY_MAX_ATTEMPTS, DB_RETRY_BASE_MS, || async {
+            collection
+                .count_documents(doc! { "status": "failed" }, None)
+                .await
+                .map_err(AppError::Database)
+        })
+        .await? as usize;
 
-        let retrying = collection
-            .count_documents(doc! { "status": "retrying" }, None)
-            .await? as usize;
+        let retrying = retry_with_backoff("dlq.count.retrying", DB_RETRY_MAX_ATTEMPTS, DB_RETRY_BASE_MS, || async {
+            collection
+                .count_documents(doc! { "status": "retrying" }, None)
+                .await
+                .map_err(AppError::Database)
+        })
+        .await? as usize;
 
         Ok(DLQStatistics {
             total,
@@ -42,6 +42,9 @@ pub enum AppError {
     #[error("User not found")]
     UserNotFound,
 
+    #[error("Device not found")]
+    DeviceNotFound,
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -53,9 +56,10 @@ impl IntoResponse for AppError {
         let status = match self {
             AppError::Authentication(_) => StatusCode::UNAUTHORIZED,
             AppError::Validation(_) => StatusCode::BAD_REQUEST,
-            AppError::UserNotFound | AppError::MessageNotFound | AppError::ConversationNotFound => {
-                StatusCode::NOT_FOUND
-            }
+            AppError::UserNotFound
+            | AppError::MessageNotFound
+            | AppError::ConversationNotFound
+            | AppError::DeviceNotFound => StatusCode::NOT_FOUND,
             AppError::DuplicateIdempotencyKey => StatusCode::CONFLICT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
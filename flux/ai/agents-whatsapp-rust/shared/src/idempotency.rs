@@ -0,0 +1,160 @@
+use crate::errors::AppResult;
+use redis::Client as RedisClient;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+const REDIS_KEY_PREFIX: &str = "idempotency:";
+
+/// Bounded, TTL-aware in-process cache of idempotency keys seen by this instance.
+/// Eviction is FIFO over insertion order, which is good enough for a hot-path
+/// dedup cache where entries are short-lived by design.
+struct LocalCache {
+    entries: HashMap<String, Instant>,
+    order: VecDeque<String>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl LocalCache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    fn contains(&mut self, key: &str) -> bool {
+        match self.entries.get(key) {
+            Some(inserted_at) if inserted_at.elapsed() < self.ttl => true,
+            Some(_) => {
+                self.entries.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, key: String) {
+        if self.entries.contains_key(&key) {
+            return;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key.clone(), Instant::now());
+        self.order.push_back(key);
+    }
+
+    /// Atomically check-then-insert under a single lock span, so that concurrent
+    /// callers racing on the same key can't all observe a miss before any of them
+    /// reserves it. Returns `true` if `key` was already present.
+    fn check_and_insert(&mut self, key: &str) -> bool {
+        if self.contains(key) {
+            return true;
+        }
+        self.insert(key.to_string());
+        false
+    }
+
+    /// Undo a reservation made by `check_and_insert`, e.g. because the Redis check that
+    /// followed it failed and the key must not be left looking "seen" when it wasn't
+    /// actually reserved anywhere durable.
+    fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+        self.order.retain(|k| k != key);
+    }
+}
+
+/// Two-tier idempotency-key cache: a bounded in-process FIFO cache checked first, backed by
+/// Redis so the check is still correct across message-processor replicas. Keeps Redis
+/// out of the hot path by populating the local cache immediately on a miss and
+/// reserving the key in Redis on a spawned background task.
+pub struct IdempotencyCache {
+    redis: RedisClient,
+    local: Mutex<LocalCache>,
+    ttl_secs: u64,
+}
+
+impl IdempotencyCache {
+    pub fn new(redis: RedisClient, capacity: usize, ttl_secs: u64) -> Self {
+        Self {
+            redis,
+            local: Mutex::new(LocalCache::new(capacity, Duration::from_secs(ttl_secs))),
+            ttl_secs,
+        }
+    }
+
+    /// Returns `true` if `key` has already been seen (a duplicate), `false` if it is
+    /// new and has been reserved for processing.
+    pub async fn check_and_reserve(&self, key: &str) -> AppResult<bool> {
+        // Reserve in the local cache before the Redis round trip (rather than after),
+        // so concurrent callers racing on the same key serialize on the mutex instead of
+        // all observing a miss and all proceeding to process it. Rolled back below if
+        // the Redis check fails, so a transient Redis error doesn't strand the key as
+        // locally "seen" and cause the broker's redelivery to be silently dropped.
+        if self.local.lock().unwrap().check_and_insert(key) {
+            return Ok(true);
+        }
+
+        let redis_key = format!("{}{}", REDIS_KEY_PREFIX, key);
+        let exists = match self.check_redis_exists(&redis_key).await {
+            Ok(exists) => exists,
+            Err(e) => {
+                self.local.lock().unwrap().remove(key);
+                return Err(e);
+            }
+        };
+
+        if exists {
+            return Ok(true);
+        }
+
+        // Reserve in Redis off the hot path; a brief window where two replicas both
+        // miss is an acceptable tradeoff given the caller's own defense-in-depth check.
+        let redis = self.redis.clone();
+        let redis_key = redis_key.clone();
+        let ttl_secs = self.ttl_secs;
+        tokio::spawn(async move {
+            let mut conn = match redis.get_multiplexed_async_connection().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("Failed to open Redis connection to reserve idempotency key: {}", e);
+                    return;
+                }
+            };
+
+            let result: redis::RedisResult<Option<String>> = redis::cmd("SET")
+                .arg(&redis_key)
+                .arg("1")
+                .arg("NX")
+                .arg("EX")
+                .arg(ttl_secs)
+                .query_async(&mut conn)
+                .await;
+
+            if let Err(e) = result {
+                warn!("Failed to reserve idempotency key in Redis: {}", e);
+            }
+        });
+
+        Ok(false)
+    }
+
+    /// Check `redis_key` with `EXISTS`, isolated so `check_and_reserve` can roll back
+    /// the local reservation on a connection/command failure without also unwinding
+    /// past the point where it needs to do so.
+    async fn check_redis_exists(&self, redis_key: &str) -> AppResult<bool> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(redis_key)
+            .query_async(&mut conn)
+            .await?;
+        Ok(exists)
+    }
+}
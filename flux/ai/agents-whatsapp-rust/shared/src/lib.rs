@@ -1,8 +1,19 @@
+pub mod delivery;
 pub mod dlq;
 pub mod errors;
+pub mod idempotency;
 pub mod models;
+pub mod prekeys;
+pub mod push;
+pub mod routing;
+pub mod telemetry;
 pub mod utils;
 
+pub use delivery::*;
 pub use dlq::*;
 pub use errors::*;
+pub use idempotency::*;
 pub use models::*;
+pub use prekeys::*;
+pub use push::*;
+pub use routing::*;
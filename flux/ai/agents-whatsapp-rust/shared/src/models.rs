@@ -83,6 +83,12 @@ pub enum WebSocketMessage {
     Migration {
         payload: MigrationPayload,
     },
+
+    // Prekey replenishment
+    PrekeysLow {
+        device_id: String,
+        remaining: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -291,6 +297,7 @@ pub enum DLQErrorType {
     Timeout,
     ServiceUnavailable,
     NetworkError,
+    PushFailed,
     Unknown,
 }
 
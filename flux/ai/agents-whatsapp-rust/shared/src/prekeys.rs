@@ -0,0 +1,173 @@
+use crate::errors::{AppError, AppResult};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+
+/// A device's X3DH key material, published so other clients can establish an E2EE
+/// session with it without a prior round trip. Mirrors `DeviceToken`'s `user_id`/
+/// `device_id` keying, but for key material instead of a push token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrekeyBundle {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_id: String,
+    pub device_id: String,
+    /// Base64-encoded Ed25519 public key, long-lived for the device's lifetime.
+    pub identity_key: String,
+    /// Base64-encoded X25519 public key, rotated periodically.
+    pub signed_prekey: String,
+    /// Base64-encoded Ed25519 signature of `signed_prekey` under `identity_key`.
+    pub signed_prekey_signature: String,
+    /// Base64-encoded X25519 public keys, each consumed by at most one `fetch`.
+    pub one_time_prekeys: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A target device's key material as handed to one requesting client. Carries at most
+/// one one-time prekey, since each must never be handed out twice.
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchedPrekeyBundle {
+    pub user_id: String,
+    pub device_id: String,
+    pub identity_key: String,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub one_time_prekey: Option<String>,
+}
+
+/// `PrekeyRegistry::fetch`'s result, pairing what the requester gets back with the pool
+/// size left behind, so the caller can decide whether to warn the device to replenish
+/// without that bookkeeping leaking into the wire payload.
+#[derive(Debug, Clone)]
+pub struct PrekeyFetchResult {
+    pub bundle: FetchedPrekeyBundle,
+    pub remaining_one_time_prekeys: usize,
+}
+
+/// MongoDB-backed registry of per-device prekey bundles, keyed by `user_id`/`device_id`.
+pub struct PrekeyRegistry {
+    db: mongodb::Database,
+}
+
+impl PrekeyRegistry {
+    pub fn new(db: mongodb::Database) -> Self {
+        Self { db }
+    }
+
+    /// Uploads or rotates a device's bundle. Rejects a `signed_prekey` whose signature
+    /// doesn't verify under `identity_key`, since a bad signature would let a
+    /// man-in-the-middle substitute their own prekey undetected.
+    pub async fn publish(
+        &self,
+        user_id: &str,
+        device_id: &str,
+        identity_key: String,
+        signed_prekey: String,
+        signed_prekey_signature: String,
+        one_time_prekeys: Vec<String>,
+    ) -> AppResult<()> {
+        verify_signed_prekey(&identity_key, &signed_prekey, &signed_prekey_signature)?;
+
+        let collection = self
+            .db
+            .collection::<mongodb::bson::Document>("prekey_bundles");
+        let filter = doc! { "user_id": user_id, "device_id": device_id };
+        let update = doc! {
+            "$set": {
+                "user_id": user_id,
+                "device_id": device_id,
+                "identity_key": &identity_key,
+                "signed_prekey": &signed_prekey,
+                "signed_prekey_signature": &signed_prekey_signature,
+                "one_time_prekeys": &one_time_prekeys,
+                "updated_at": Utc::now(),
+            },
+        };
+        let options = mongodb::options::UpdateOptions::builder()
+            .upsert(true)
+            .build();
+        collection.update_one(filter, update, options).await?;
+        Ok(())
+    }
+
+    /// Returns `device_id`'s bundle, atomically popping one one-time prekey off the
+    /// front of its pool so it's never handed out to two requesters. Falls back to
+    /// signed-prekey-only once the pool is empty.
+    pub async fn fetch(
+        &self,
+        user_id: &str,
+        device_id: &str,
+    ) -> AppResult<Option<PrekeyFetchResult>> {
+        let collection = self.db.collection::<PrekeyBundle>("prekey_bundles");
+        let filter = doc! { "user_id": user_id, "device_id": device_id };
+        // $pop removes the first array element in a single atomic document update, so
+        // two concurrent fetches can never walk away with the same one-time prekey.
+        let update = doc! { "$pop": { "one_time_prekeys": -1_i32 } };
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(mongodb::options::ReturnDocument::Before)
+            .build();
+
+        let Some(bundle) = collection
+            .find_one_and_update(filter, update, options)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut one_time_prekeys = bundle.one_time_prekeys;
+        let one_time_prekey = if one_time_prekeys.is_empty() {
+            None
+        } else {
+            Some(one_time_prekeys.remove(0))
+        };
+
+        Ok(Some(PrekeyFetchResult {
+            bundle: FetchedPrekeyBundle {
+                user_id: bundle.user_id,
+                device_id: bundle.device_id,
+                identity_key: bundle.identity_key,
+                signed_prekey: bundle.signed_prekey,
+                signed_prekey_signature: bundle.signed_prekey_signature,
+                one_time_prekey,
+            },
+            remaining_one_time_prekeys: one_time_prekeys.len(),
+        }))
+    }
+}
+
+/// Verifies `signed_prekey_signature` is a valid Ed25519 signature of `signed_prekey`
+/// under `identity_key`, all base64-encoded over the wire.
+fn verify_signed_prekey(
+    identity_key: &str,
+    signed_prekey: &str,
+    signed_prekey_signature: &str,
+) -> AppResult<()> {
+    let identity_key_bytes = BASE64
+        .decode(identity_key)
+        .map_err(|e| AppError::Validation(format!("invalid identity_key encoding: {}", e)))?;
+    let identity_key_bytes: [u8; 32] = identity_key_bytes
+        .try_into()
+        .map_err(|_| AppError::Validation("identity_key must be 32 bytes".to_string()))?;
+    let verifying_key = VerifyingKey::from_bytes(&identity_key_bytes)
+        .map_err(|e| AppError::Validation(format!("invalid identity_key: {}", e)))?;
+
+    let signed_prekey_bytes = BASE64
+        .decode(signed_prekey)
+        .map_err(|e| AppError::Validation(format!("invalid signed_prekey encoding: {}", e)))?;
+
+    let signature_bytes = BASE64.decode(signed_prekey_signature).map_err(|e| {
+        AppError::Validation(format!("invalid signed_prekey_signature encoding: {}", e))
+    })?;
+    let signature_bytes: [u8; 64] = signature_bytes.try_into().map_err(|_| {
+        AppError::Validation("signed_prekey_signature must be 64 bytes".to_string())
+    })?;
+    let signature = Signature::from_bytes(&signature_bytes);
+
+    verifying_key
+        .verify(&signed_prekey_bytes, &signature)
+        .map_err(|_| AppError::Validation("signed prekey signature verification failed".to_string()))
+}
@@ -0,0 +1,201 @@
+use crate::errors::{AppError, AppResult};
+use crate::models::MessageType;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+
+/// A device registered to receive push notifications for a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_id: String,
+    pub device_id: String,
+    pub push_token: String,
+    pub platform: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A push job enqueued when a receiver has no live WebSocket connection. Carries just
+/// enough for the client to wake up and fetch the message rather than the message
+/// content itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushJob {
+    pub user_id: String,
+    pub conversation_id: String,
+    pub message_id: String,
+    pub message_type: MessageType,
+    /// When true (E2EE content), the push carries only a data-payload wake-up with no
+    /// visible title/body, so plaintext never reaches the push provider.
+    pub always_encrypted: bool,
+}
+
+/// Outcome of dispatching a `PushJob` to a receiver's registered devices.
+#[derive(Debug, Clone)]
+pub enum PushOutcome {
+    Delivered,
+    NoRegisteredDevices,
+    Failed(String),
+}
+
+/// A push notification backend. Implemented for FCM; swappable for APNs or a test
+/// double without touching the dispatch logic.
+#[async_trait]
+pub trait PushProvider: Send + Sync {
+    async fn send(&self, push_token: &str, job: &PushJob) -> AppResult<()>;
+}
+
+/// FCM push provider: sends a high-priority, collapsible data message so a receiver's
+/// client wakes up and fetches the new message over its own authenticated channel.
+pub struct FcmPushProvider {
+    http: reqwest::Client,
+    server_key: String,
+    endpoint: String,
+}
+
+impl FcmPushProvider {
+    pub fn new(server_key: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            server_key,
+            endpoint: "https://fcm.googleapis.com/fcm/send".to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl PushProvider for FcmPushProvider {
+    async fn send(&self, push_token: &str, job: &PushJob) -> AppResult<()> {
+        let mut payload = serde_json::json!({
+            "to": push_token,
+            "priority": "high",
+            "collapse_key": format!("conv:{}", job.conversation_id),
+            "data": {
+                "conversation_id": job.conversation_id,
+                "message_id": job.message_id,
+                "message_type": job.message_type,
+            },
+        });
+
+        // Encrypted content never gets a visible notification payload from FCM itself;
+        // the client decrypts and renders its own notification after waking up.
+        if !job.always_encrypted {
+            payload["notification"] = serde_json::json!({
+                "title": "New message",
+                "body": "You have a new message",
+            });
+        }
+
+        let response = self
+            .http
+            .post(&self.endpoint)
+            .header("Authorization", format!("key={}", self.server_key))
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("FCM send failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AppError::Internal(format!(
+                "FCM returned error status: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// MongoDB-backed registry of device tokens, keyed by `user_id`/`device_id`.
+pub struct DeviceTokenRegistry {
+    db: mongodb::Database,
+}
+
+impl DeviceTokenRegistry {
+    pub fn new(db: mongodb::Database) -> Self {
+        Self { db }
+    }
+
+    /// Register or rotate a device's push token.
+    pub async fn register(
+        &self,
+        user_id: &str,
+        device_id: &str,
+        push_token: &str,
+        platform: &str,
+    ) -> AppResult<()> {
+        let collection = self.db.collection::<mongodb::bson::Document>("device_tokens");
+        let filter = doc! { "user_id": user_id, "device_id": device_id };
+        let update = doc! {
+            "$set": {
+                "user_id": user_id,
+                "device_id": device_id,
+                "push_token": push_token,
+                "platform": platform,
+            },
+            "$setOnInsert": { "created_at": Utc::now() },
+        };
+        let options = mongodb::options::UpdateOptions::builder()
+            .upsert(true)
+            .build();
+        collection.update_one(filter, update, options).await?;
+        Ok(())
+    }
+
+    pub async fn tokens_for_user(&self, user_id: &str) -> AppResult<Vec<DeviceToken>> {
+        let collection = self.db.collection::<DeviceToken>("device_tokens");
+        let mut cursor = collection.find(doc! { "user_id": user_id }, None).await?;
+        let mut tokens = Vec::new();
+        while cursor.advance().await? {
+            tokens.push(cursor.deserialize_current()?);
+        }
+        Ok(tokens)
+    }
+}
+
+/// Fans a `PushJob` out to every device a receiver has registered, recording the
+/// overall delivery outcome so the caller can feed failures into the DLQ.
+pub struct PushDispatcher {
+    provider: Arc<dyn PushProvider>,
+    registry: DeviceTokenRegistry,
+}
+
+impl PushDispatcher {
+    pub fn new(provider: Arc<dyn PushProvider>, registry: DeviceTokenRegistry) -> Self {
+        Self { provider, registry }
+    }
+
+    pub async fn dispatch(&self, job: PushJob) -> AppResult<PushOutcome> {
+        let tokens = self.registry.tokens_for_user(&job.user_id).await?;
+        if tokens.is_empty() {
+            return Ok(PushOutcome::NoRegisteredDevices);
+        }
+
+        let mut delivered = false;
+        let mut last_error = None;
+
+        for token in &tokens {
+            match self.provider.send(&token.push_token, &job).await {
+                Ok(()) => delivered = true,
+                Err(e) => {
+                    warn!(
+                        "Push send failed for user={} device={}: {}",
+                        job.user_id, token.device_id, e
+                    );
+                    last_error = Some(e.to_string());
+                }
+            }
+        }
+
+        if delivered {
+            Ok(PushOutcome::Delivered)
+        } else {
+            Ok(PushOutcome::Failed(
+                last_error.unwrap_or_else(|| "unknown push failure".to_string()),
+            ))
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::errors::AppResult;
+use crate::models::MessageType;
+use async_trait::async_trait;
+use mongodb::bson::doc;
+use mongodb::Database;
+use serde::{Deserialize, Serialize};
+
+/// Everything a routing strategy needs to pick an agent for an inbound message.
+pub struct RoutingContext {
+    pub conversation_id: String,
+    pub user_id: String,
+    pub message_type: MessageType,
+    /// Message text available for classification — plaintext for unencrypted message
+    /// types, or gateway-visible metadata (e.g. a caption) when `content` is E2EE.
+    pub content: String,
+}
+
+/// A candidate agent assignment with how confident the strategy that produced it is.
+#[derive(Debug, Clone)]
+pub struct AgentRoute {
+    pub agent_id: String,
+    pub confidence: f64,
+}
+
+/// A single routing strategy. Strategies are tried in priority order by
+/// `AgentRouterChain` until one returns a route at or above the configured confidence
+/// threshold.
+#[async_trait]
+pub trait AgentRouter: Send + Sync {
+    async fn route(&self, ctx: &RoutingContext) -> AppResult<Option<AgentRoute>>;
+}
+
+/// Looks up an explicit per-user agent preference, set e.g. from a settings screen.
+/// Always confident when a preference exists: the user asked for this agent directly.
+pub struct ExplicitPreferenceRouter {
+    db: Database,
+}
+
+impl ExplicitPreferenceRouter {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl AgentRouter for ExplicitPreferenceRouter {
+    async fn route(&self, ctx: &RoutingContext) -> AppResult<Option<AgentRoute>> {
+        let collection = self
+            .db
+            .collection::<mongodb::bson::Document>("user_agent_preferences");
+        let filter = doc! { "_id": &ctx.user_id };
+
+        let preference = collection.find_one(filter, None).await?;
+        Ok(preference
+            .and_then(|doc| doc.get_str("agent_id").map(String::from).ok())
+            .map(|agent_id| AgentRoute {
+                agent_id,
+                confidence: 1.0,
+            }))
+    }
+}
+
+/// A single `keyword -> agent_id` mapping used by `KeywordIntentClassifier`. Loaded
+/// from config so new agents can be onboarded without a code change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeywordRule {
+    pub keyword: String,
+    pub agent_id: String,
+}
+
+/// Classifies message text against a configurable set of keyword rules. Confidence is
+/// the fraction of the message's words that matched the winning rule's keyword, which
+/// is crude but cheap and good enough to gate the fallback to the next strategy.
+pub struct KeywordIntentClassifier {
+    rules: Vec<KeywordRule>,
+}
+
+impl KeywordIntentClassifier {
+    pub fn new(rules: Vec<KeywordRule>) -> Self {
+        Self { rules }
+    }
+}
+
+#[async_trait]
+impl AgentRouter for KeywordIntentClassifier {
+    async fn route(&self, ctx: &RoutingContext) -> AppResult<Option<AgentRoute>> {
+        if ctx.message_type != MessageType::Text || ctx.content.is_empty() {
+            return Ok(None);
+        }
+
+        let lower = ctx.content.to_lowercase();
+        let word_count = lower.split_whitespace().count().max(1) as f64;
+
+        let best = self
+            .rules
+            .iter()
+            .filter(|rule| lower.contains(&rule.keyword.to_lowercase()))
+            .max_by(|a, b| a.keyword.len().cmp(&b.keyword.len()));
+
+        Ok(best.map(|rule| {
+            let matched_words = rule.keyword.split_whitespace().count().max(1) as f64;
+            AgentRoute {
+                agent_id: rule.agent_id.clone(),
+                confidence: (matched_words / word_count).min(1.0),
+            }
+        }))
+    }
+}
+
+/// Keeps a conversation pinned to whichever agent it was previously routed to, so a
+/// thread doesn't bounce between agents message to message.
+pub struct StickyConversationRouter {
+    db: Database,
+}
+
+impl StickyConversationRouter {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl AgentRouter for StickyConversationRouter {
+    async fn route(&self, ctx: &RoutingContext) -> AppResult<Option<AgentRoute>> {
+        let collection = self
+            .db
+            .collection::<mongodb::bson::Document>("conversations");
+        let filter = doc! { "_id": &ctx.conversation_id };
+
+        let conversation = collection.find_one(filter, None).await?;
+        Ok(conversation
+            .and_then(|doc| doc.get_str("agent_id").map(String::from).ok())
+            .map(|agent_id| AgentRoute {
+                agent_id,
+                confidence: 1.0,
+            }))
+    }
+}
+
+/// Always matches, with confidence `1.0`, so the chain always terminates.
+pub struct DefaultAgentRouter {
+    default_agent_id: String,
+}
+
+impl DefaultAgentRouter {
+    pub fn new(default_agent_id: String) -> Self {
+        Self { default_agent_id }
+    }
+}
+
+#[async_trait]
+impl AgentRouter for DefaultAgentRouter {
+    async fn route(&self, _ctx: &RoutingContext) -> AppResult<Option<AgentRoute>> {
+        Ok(Some(AgentRoute {
+            agent_id: self.default_agent_id.clone(),
+            confidence: 1.0,
+        }))
+    }
+}
+
+/// Tries each strategy in priority order, stopping at the first route whose
+/// confidence meets `confidence_threshold`. A strategy that returns `None` or a
+/// below-threshold route is treated as "no opinion" and the chain falls through to the
+/// next one.
+pub struct AgentRouterChain {
+    strategies: Vec<Box<dyn AgentRouter>>,
+    confidence_threshold: f64,
+}
+
+impl AgentRouterChain {
+    pub fn new(strategies: Vec<Box<dyn AgentRouter>>, confidence_threshold: f64) -> Self {
+        Self {
+            strategies,
+            confidence_threshold,
+        }
+    }
+
+    pub async fn resolve(&self, ctx: &RoutingContext) -> AppResult<String> {
+        for strategy in &self.strategies {
+            if let Some(route) = strategy.route(ctx).await? {
+                if route.confidence >= self.confidence_threshold {
+                    return Ok(route.agent_id);
+                }
+            }
+        }
+
+        // Every chain should end in a `DefaultAgentRouter`, but fall back to a literal
+        // default rather than panicking if a custom chain omits one.
+        Ok("agent-bruno".to_string())
+    }
+}
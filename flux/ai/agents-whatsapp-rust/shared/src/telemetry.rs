@@ -0,0 +1,161 @@
+use once_cell::sync::OnceCell;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::Sampler;
+use opentelemetry_sdk::{runtime, trace as sdktrace, Resource};
+use std::collections::HashMap;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Where OTLP traces/metrics/logs are exported and how aggressively traces are sampled.
+/// Wired from `Config` so it can be tuned per-environment without a code change.
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    pub otlp_endpoint: String,
+    pub sampling_ratio: f64,
+}
+
+/// Counters and histograms shared across the routing pipeline. Created once at startup
+/// and looked up via [`metrics()`] from wherever a span is recording an event, the same
+/// lazily initialized static-behind-a-getter pattern `utils::retry_metrics` uses for
+/// in-process state.
+pub struct RoutingMetrics {
+    pub duplicate_messages: Counter<u64>,
+    pub publish_retries: Counter<u64>,
+    pub dlq_insertions: Counter<u64>,
+    pub publish_latency_ms: Histogram<f64>,
+    pub routing_latency_ms: Histogram<f64>,
+    /// DLQ entries the reaper successfully redelivered to the broker.
+    pub dlq_replayed: Counter<u64>,
+    /// DLQ entries resolved after a successful reaper replay.
+    pub dlq_resolved: Counter<u64>,
+    /// DLQ entries that exhausted `max_retries` and were left `Failed`.
+    pub dlq_exhausted: Counter<u64>,
+}
+
+static METRICS: OnceCell<RoutingMetrics> = OnceCell::new();
+
+/// Returns the process-wide routing metrics, initialized on first access against
+/// whatever global meter provider [`init_telemetry`] installed.
+pub fn metrics() -> &'static RoutingMetrics {
+    METRICS.get_or_init(|| {
+        let meter = global::meter("message-processor");
+        RoutingMetrics {
+            duplicate_messages: meter
+                .u64_counter("routing.duplicate_messages")
+                .with_description("Messages short-circuited by the idempotency cache")
+                .init(),
+            publish_retries: meter
+                .u64_counter("routing.publish_retries")
+                .with_description("Broker publish attempts beyond the first")
+                .init(),
+            dlq_insertions: meter
+                .u64_counter("routing.dlq_insertions")
+                .with_description("Messages written to the dead letter queue")
+                .init(),
+            publish_latency_ms: meter
+                .f64_histogram("routing.publish_latency_ms")
+                .with_description("Time to publish a single event to the broker")
+                .init(),
+            routing_latency_ms: meter
+                .f64_histogram("routing.end_to_end_latency_ms")
+                .with_description("Time from inbound CloudEvent to broker publish success")
+                .init(),
+            dlq_replayed: meter
+                .u64_counter("dlq.replayed")
+                .with_description("DLQ entries redelivered to the broker by the reaper")
+                .init(),
+            dlq_resolved: meter
+                .u64_counter("dlq.resolved")
+                .with_description("DLQ entries resolved after a successful reaper replay")
+                .init(),
+            dlq_exhausted: meter
+                .u64_counter("dlq.exhausted")
+                .with_description("DLQ entries that exhausted max_retries")
+                .init(),
+        }
+    })
+}
+
+/// Installs OTLP trace and metric export and sets the global tracing subscriber.
+/// Traces and metrics are exported over OTLP/gRPC to `config.otlp_endpoint`; logs are
+/// not exported over OTLP: they go to stdout through the `fmt` layer, with events
+/// recorded on the active span via `tracing-opentelemetry`. Called once from `main`, in place of a bare
+/// `tracing_subscriber::fmt().init()`.
+pub fn init_telemetry(service_name: &str, config: &TelemetryConfig) -> anyhow::Result<()> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let resource = Resource::new(vec![KeyValue::new("service.name", service_name.to_string())]);
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&config.otlp_endpoint),
+        )
+        .with_trace_config(
+            sdktrace::config()
+                .with_sampler(Sampler::TraceIdRatioBased(config.sampling_ratio))
+                .with_resource(resource.clone()),
+        )
+        .install_batch(runtime::Tokio)?;
+
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::Tokio)
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&config.otlp_endpoint),
+        )
+        .with_resource(resource)
+        .build()?;
+    global::set_meter_provider(meter_provider);
+
+    let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer);
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::new("message_processor=info"))
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .try_init()?;
+
+    Ok(())
+}
+
+/// Carries CloudEvent `traceparent`/`tracestate` extension attributes so the W3C
+/// trace-context propagator can extract them the same way it would from HTTP headers.
+struct CloudEventExtractor<'a>(&'a HashMap<&'static str, String>);
+
+impl<'a> Extractor for CloudEventExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|v| v.as_str())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().copied().collect()
+    }
+}
+
+/// Extracts the remote span context carried on an inbound CloudEvent's `traceparent`/
+/// `tracestate` extension attributes, so the root span created for that event is a
+/// child of whatever produced the message rather than starting a new trace.
+pub fn context_from_cloudevent_headers(
+    traceparent: Option<String>,
+    tracestate: Option<String>,
+) -> opentelemetry::Context {
+    let mut carrier = HashMap::new();
+    if let Some(traceparent) = traceparent {
+        carrier.insert("traceparent", traceparent);
+    }
+    if let Some(tracestate) = tracestate {
+        carrier.insert("tracestate", tracestate);
+    }
+
+    let propagator = TraceContextPropagator::new();
+    propagator.extract(&CloudEventExtractor(&carrier))
+}
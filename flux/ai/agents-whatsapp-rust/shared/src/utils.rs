@@ -1,3 +1,8 @@
+use crate::errors::{AppError, AppResult};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
 use uuid::Uuid;
 
 /// Generate a new UUID v4 for idempotency keys
@@ -19,3 +24,94 @@ pub fn generate_conversation_id() -> String {
 pub fn generate_session_id() -> String {
     format!("sess_{}", Uuid::new_v4())
 }
+
+/// Failed attempts and last error observed per retry-helper tag, for lightweight
+/// introspection into which operations are retrying under transient failure.
+#[derive(Debug, Clone, Default)]
+pub struct RetryMetric {
+    pub attempts: u64,
+    pub last_error: Option<String>,
+}
+
+fn retry_metrics() -> &'static Mutex<HashMap<String, RetryMetric>> {
+    static METRICS: OnceLock<Mutex<HashMap<String, RetryMetric>>> = OnceLock::new();
+    METRICS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Snapshot of the retry metrics recorded under `tag`, if any attempt under it has failed.
+pub fn retry_metric_snapshot(tag: &str) -> Option<RetryMetric> {
+    retry_metrics().lock().unwrap().get(tag).cloned()
+}
+
+fn record_retry_attempt(tag: &str, error: &str) {
+    let mut metrics = retry_metrics().lock().unwrap();
+    let metric = metrics.entry(tag.to_string()).or_default();
+    metric.attempts += 1;
+    metric.last_error = Some(error.to_string());
+}
+
+/// Transient failures (a brief MongoDB leader change, a dropped Redis connection) are
+/// worth retrying; everything else (a duplicate key, a rejected command) will just fail
+/// the same way again and should propagate immediately instead of burning `max_attempts`.
+fn is_transient(err: &AppError) -> bool {
+    match err {
+        AppError::Database(e) => is_transient_mongo(e),
+        AppError::Redis(e) => e.is_connection_error() || e.is_timeout(),
+        _ => false,
+    }
+}
+
+/// MongoDB failures worth retrying: network/connection issues and server-selection
+/// timeouts (e.g. during a leader election), as opposed to command errors like a
+/// duplicate key or a validation failure that will fail identically on every attempt.
+fn is_transient_mongo(err: &mongodb::error::Error) -> bool {
+    if err.contains_label("RetryableWriteError") {
+        return true;
+    }
+
+    matches!(
+        *err.kind,
+        mongodb::error::ErrorKind::Io(_)
+            | mongodb::error::ErrorKind::ServerSelection { .. }
+            | mongodb::error::ErrorKind::ConnectionPoolCleared { .. }
+    )
+}
+
+/// Retry an async operation with exponential backoff (capped at 30s, same as
+/// `DeadLetterQueue::calculate_backoff`) for the classified-transient subset of
+/// `AppError`, recording the number of attempts and last error under `tag`.
+pub async fn retry_with_backoff<F, Fut, T>(
+    tag: &str,
+    max_attempts: u32,
+    base_ms: u64,
+    mut op: F,
+) -> AppResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = AppResult<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if is_transient(&e) && attempt + 1 < max_attempts => {
+                record_retry_attempt(tag, &e.to_string());
+                let backoff_ms = ((base_ms as f64) * 2f64.powi(attempt as i32)).min(30_000.0) as u64;
+                warn!(
+                    "{}: transient error on attempt {}/{}: {}. Retrying in {}ms",
+                    tag,
+                    attempt + 1,
+                    max_attempts,
+                    e,
+                    backoff_ms
+                );
+                tokio::time::sleep(std::time::Duration::from_millis(backoff_ms)).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                record_retry_attempt(tag, &e.to_string());
+                return Err(e);
+            }
+        }
+    }
+}